@@ -1,69 +1,159 @@
 pub mod merkle_tree {
 
-    use crypto::digest::Digest;
-    use crypto::sha2::Sha256;
+    use digest::Digest;
     use rand::Rng;
+    use sha2::Sha256;
+    use std::collections::HashMap;
+    use std::marker::PhantomData;
     use std::result::Result;
     use std::vec::Vec;
 
-    // hash function to be used for the construction of the merkle tree
-    pub fn hash_leaf(leaf: &str) -> String {
-        let mut hasher = Sha256::new();
-        hasher.input_str(leaf);
-        hasher.result_str()
+    // hash function to be used for the construction of the merkle tree.
+    // generic over the digest so callers can pick BLAKE3, Keccak-256, SHA-512, ...;
+    // the raw digest bytes are returned and only hex-encoded at the API boundary.
+    pub fn hash_leaf<D: Digest>(leaf: &str) -> Vec<u8> {
+        let mut hasher = D::new();
+        hasher.update(leaf.as_bytes());
+        hasher.finalize().to_vec()
     }
 
     // hash function to be used for the construction of the merkle tree
-    pub fn hash_node(left: &str, right: &str) -> String {
-        let mut hasher = Sha256::new();
-        hasher.input_str(left);
-        hasher.input_str(right);
-        hasher.result_str()
+    pub fn hash_node<D: Digest>(left: &[u8], right: &[u8]) -> Vec<u8> {
+        let mut hasher = D::new();
+        hasher.update(left);
+        hasher.update(right);
+        hasher.finalize().to_vec()
     }
 
     pub enum Node {
         Empty,
         Leaf {
-            hash: String,
+            hash: Vec<u8>,
             data: String,
         },
         Branch {
-            hash: String,
+            hash: Vec<u8>,
             left: Box<Node>,
             right: Box<Node>,
         },
     }
 
-    pub struct MerkleTree {
-        root: Node,
+    // the classic (fully-padded) tree is stored as a single flat array of node
+    // hashes laid out level by level, leaves first then each parent level. this
+    // removes pointer chasing and deep recursion, gives O(1) access to any leaf by
+    // index, and lets get_proof/update_element walk up by index arithmetic
+    // (parent = i / 2, sibling = i ^ 1) instead of a binary-string traversal.
+    pub struct MerkleTree<D = Sha256> {
+        nodes: Vec<Vec<u8>>,       // all node hashes, level by level (leaves first)
+        leaves: Vec<String>,       // the padded leaf elements, for O(1) leaf lookup
+        level_offsets: Vec<usize>, // start index of each level within `nodes`
+        _marker: PhantomData<D>,
     }
 
-    pub struct MerkleProof {
-        pub element: String,       // element for which we want to prove inclusion
-        pub siblings: Vec<String>, // path of siblings from the element up to the root
-        pub directions: Vec<bool>, // signal if the sibling at the same index is on the left or right
+    // length of the level above one of `len` nodes: a single node is already the
+    // root (no level above it), otherwise the nodes pair up.
+    fn next_level_len(len: usize) -> usize {
+        if len == 1 {
+            0
+        } else {
+            len.div_ceil(2)
+        }
     }
 
-    pub fn get_root(mt: &MerkleTree) -> String {
-        match &mt.root {
-            Node::Empty => String::new(),
-            Node::Leaf { hash, .. } => hash.to_string(),
-            Node::Branch { hash, .. } => hash.to_string(),
+    // total number of node hashes stored across every level of a tree with the
+    // given number of leaves, used to size the flat array up front.
+    fn total_capacity(num_leaves: usize) -> usize {
+        let mut total = 0;
+        let mut len = num_leaves;
+        while len > 0 {
+            total += len;
+            len = next_level_len(len);
+        }
+        total
+    }
+
+    pub struct MerkleProof<D = Sha256> {
+        pub element: String,         // element for which we want to prove inclusion
+        pub siblings: Vec<Vec<u8>>,  // path of siblings from the element up to the root
+        pub directions: Vec<bool>,   // signal if the sibling at the same index is on the left or right
+        pub _marker: PhantomData<D>, // binds the proof to the digest it was produced with
+    }
+
+    // a single compressed proof for every leaf in [start_index, end_index).
+    //
+    // instead of one sibling path per covered leaf, only the boundary siblings
+    // that fall *outside* the covered range are stored: every sibling hash that
+    // lies inside the range can be recomputed from the covered elements during
+    // verification. each stored sibling carries the level it lives on and whether
+    // it sits to the left of the covered interval (so the verifier can splice it
+    // back in the right order).
+    pub struct AggregateProof<D = Sha256> {
+        pub start_index: usize,    // index of the first covered element
+        pub elements: Vec<String>, // the covered elements, in order
+        pub siblings: Vec<AggregateSibling>,
+        pub _marker: PhantomData<D>,
+    }
+
+    pub struct AggregateSibling {
+        pub level: usize, // 0 at the leaves, increasing towards the root
+        pub left: bool,   // true if this sibling lies to the left of the covered interval
+        pub hash: Vec<u8>,
+    }
+
+    pub fn get_root<D>(mt: &MerkleTree<D>) -> String {
+        match mt.nodes.last() {
+            Some(hash) => hex::encode(hash),
+            None => String::new(),
         }
     }
 
     // create a merkle tree from a list of elements
     // the tree should have the minimum height needed to contain all elements
     // empty slots should be filled with an empty string
-    pub fn create_merkle_tree(elements: &Vec<String>) -> Result<MerkleTree, String> {
+    pub fn create_merkle_tree<D: Digest>(elements: &Vec<String>) -> Result<MerkleTree<D>, String> {
         if elements.is_empty() {
-            return Ok(MerkleTree { root: Node::Empty });
+            return Ok(MerkleTree {
+                nodes: Vec::new(),
+                leaves: Vec::new(),
+                level_offsets: Vec::new(),
+                _marker: PhantomData,
+            });
         }
 
-        let padded_elements = pad_elements(elements);
+        let leaves = pad_elements(elements);
+        let num_leaves = leaves.len();
+
+        let mut nodes = Vec::with_capacity(total_capacity(num_leaves));
+        let mut level_offsets = Vec::new();
 
-        let root = create_node(&padded_elements);
-        Ok(MerkleTree { root })
+        // level 0: the leaf hashes
+        level_offsets.push(0);
+        for element in &leaves {
+            nodes.push(hash_leaf::<D>(element));
+        }
+
+        // each subsequent level hashes adjacent pairs of the level below it
+        let mut level_start = 0;
+        let mut level_len = num_leaves;
+        while level_len > 1 {
+            level_offsets.push(nodes.len());
+            let mut i = 0;
+            while i < level_len {
+                let left = nodes[level_start + i].clone();
+                let right = nodes[level_start + i + 1].clone();
+                nodes.push(hash_node::<D>(&left, &right));
+                i += 2;
+            }
+            level_start += level_len;
+            level_len = next_level_len(level_len);
+        }
+
+        Ok(MerkleTree {
+            nodes,
+            leaves,
+            level_offsets,
+            _marker: PhantomData,
+        })
     }
 
     // helper function to fill empty slots with empty strings
@@ -86,51 +176,6 @@ pub mod merkle_tree {
         padded_elements
     }
 
-    fn create_node(elements: &[String]) -> Node {
-        if elements.len() == 1 {
-            println!(
-                "creating node \"{}\" with hash: {}",
-                elements[0].clone(),
-                hash_leaf(&elements[0])
-            );
-            return Node::Leaf {
-                hash: hash_leaf(&elements[0]),
-                data: elements[0].clone(),
-            };
-        }
-
-        let mid = elements.len() / 2;
-        let left = create_node(&elements[0..mid]);
-        let right = create_node(&elements[mid..]);
-
-        let hash = hash_node(
-            match &left {
-                Node::Leaf { hash, .. } | Node::Branch { hash, .. } => hash,
-                Node::Empty => "",
-            },
-            match &right {
-                Node::Leaf { hash, .. } | Node::Branch { hash, .. } => hash,
-                Node::Empty => "",
-            },
-        );
-        println!("creating branch with hash: {}\n", hash);
-
-        Node::Branch {
-            hash: hash_node(
-                match &left {
-                    Node::Leaf { hash, .. } | Node::Branch { hash, .. } => hash,
-                    Node::Empty => "",
-                },
-                match &right {
-                    Node::Leaf { hash, .. } | Node::Branch { hash, .. } => hash,
-                    Node::Empty => "",
-                },
-            ),
-            left: Box::new(left),
-            right: Box::new(right),
-        }
-    }
-
     // return a merkle proof of the inclusion of element at the given index
     //
     // example:
@@ -146,86 +191,164 @@ pub mod merkle_tree {
     // element    = E
     // siblings   = [d3-3, d2-0, d1-1]
     // directions = [false, true, false]
-    pub fn get_proof(t: &MerkleTree, index: usize) -> Result<MerkleProof, String> {
-        let root = &t.root;
-        let elements = collect_elements(root);
-        let num_elements = elements.len();
+    pub fn get_proof<D>(t: &MerkleTree<D>, index: usize) -> Result<MerkleProof<D>, String> {
+        let num_leaves = t.leaves.len();
 
-        if index >= num_elements {
+        if index >= num_leaves {
             return Err(String::from("Index out of bounds"));
         }
 
-        let element = elements[index].clone();
+        let element = t.leaves[index].clone();
         let mut siblings = Vec::new();
         let mut directions = Vec::new();
 
-        let mut current_node = root;
-        let h = (num_elements as f32).log2() as usize;
-
-        let b_str = format!("{:0h$b}", index, h = h);
-        let b_vec: Vec<_> = b_str.chars().map(|c| c.to_digit(2).unwrap()).collect();
-
-        for b in &b_vec {
-            if let Node::Branch { left, right, .. } = current_node {
-                let (sibling_node, next_node, direction) = if *b == 0 {
-                    (right, left, true)
-                } else {
-                    (left, right, false)
-                };
-
-                match &**sibling_node {
-                    Node::Branch { hash, .. } => {
-                        siblings.push(hash.clone());
-                        directions.push(direction);
-                    }
-                    Node::Leaf { hash, .. } => {
-                        siblings.push(hash.clone());
-                        directions.push(direction);
-                    }
-                    Node::Empty => return Err(String::from("Invalid sibling node type")),
-                }
-
-                current_node = next_node;
-            }
+        // walk up from the leaf, recording each sibling by index arithmetic
+        let mut i = index;
+        let mut level = 0;
+        while level + 1 < t.level_offsets.len() {
+            let level_start = t.level_offsets[level];
+            let sibling = i ^ 1;
+            siblings.push(t.nodes[level_start + sibling].clone());
+            // an even index is a left child, so its sibling is on the right
+            directions.push(i.is_multiple_of(2));
+            i /= 2;
+            level += 1;
         }
 
-        siblings.reverse();
-        directions.reverse();
-
         Ok(MerkleProof {
             element,
             siblings,
             directions,
+            _marker: PhantomData,
         })
     }
 
-    // Helper function to collect leaf nodes' elements in-order
-    fn collect_elements(node: &Node) -> Vec<String> {
-        match node {
-            Node::Leaf { data, .. } => vec![data.clone()],
-            Node::Branch { left, right, .. } => {
-                let mut elements = collect_elements(left);
-                elements.extend(collect_elements(right));
-                elements
+    // verify a merkle tree against a known root (hex-encoded)
+    pub fn verify_proof<D: Digest>(root: String, proof: &MerkleProof<D>) -> bool {
+        let mut current_hash = hash_leaf::<D>(&proof.element);
+
+        for (sibling_hash, direction) in proof.siblings.iter().zip(proof.directions.iter()) {
+            if *direction {
+                current_hash = hash_node::<D>(&current_hash, sibling_hash);
+            } else {
+                current_hash = hash_node::<D>(sibling_hash, &current_hash);
             }
-            Node::Empty => Vec::new(),
         }
+
+        hex::encode(current_hash) == root
     }
 
-    // verify a merkle tree against a known root
-    pub fn verify_proof(root: String, proof: &MerkleProof) -> bool {
-        let mut current_hash = hash_leaf(&proof.element);
+    impl<D: Digest> MerkleProof<D> {
+        // Encode a proof with a fixed, self-describing layout so it can be
+        // transmitted or stored compactly and fed straight back into verify_proof:
+        //
+        //   [ 0.. 8)  u64 LE  sibling count `n`
+        //   [ 8..16)  u64 LE  element byte length `m`
+        //   [16..16+m)        the element bytes (UTF-8)
+        //   next n * D::output_size() bytes: the sibling hashes, leaf to root
+        //   final ceil(n/8) bytes: the `directions` bitfield, bit i % 8 of byte
+        //                          i / 8 (LSB first) set when directions[i] is true
+        pub fn to_bytes(&self) -> Vec<u8> {
+            let mut out = Vec::new();
+            out.extend_from_slice(&(self.siblings.len() as u64).to_le_bytes());
 
-        for (sibling_hash, direction) in proof.siblings.iter().zip(proof.directions.iter()) {
-            if *direction {
-                current_hash = hash_node(&current_hash, sibling_hash);
-            } else {
-                current_hash = hash_node(sibling_hash, &current_hash);
+            let element = self.element.as_bytes();
+            out.extend_from_slice(&(element.len() as u64).to_le_bytes());
+            out.extend_from_slice(element);
+
+            for sibling in &self.siblings {
+                out.extend_from_slice(sibling);
+            }
+
+            let mut byte = 0u8;
+            for (i, direction) in self.directions.iter().enumerate() {
+                if *direction {
+                    byte |= 1 << (i % 8);
+                }
+                if i % 8 == 7 {
+                    out.push(byte);
+                    byte = 0;
+                }
+            }
+            if !self.directions.len().is_multiple_of(8) {
+                out.push(byte);
+            }
+
+            out
+        }
+
+        // Decode a proof produced by `to_bytes`. Each sibling hash is read as
+        // `D::output_size()` raw bytes, so the digest must match the one that
+        // produced the proof.
+        pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+            let digest_len = <D as Digest>::output_size();
+
+            if bytes.len() < 16 {
+                return Err(String::from("truncated proof header"));
+            }
+            let n = u64::from_le_bytes(bytes[0..8].try_into().unwrap()) as usize;
+            let m = u64::from_le_bytes(bytes[8..16].try_into().unwrap()) as usize;
+
+            // every length below comes straight off the wire, so bounds-check with
+            // checked arithmetic before slicing: an attacker-supplied n or m near
+            // usize::MAX would otherwise overflow the `+`/`*` below (panicking in
+            // debug, wrapping and then indexing out of bounds in release).
+            let offset = 16usize;
+            let after_element = offset
+                .checked_add(m)
+                .ok_or_else(|| String::from("element length overflow"))?;
+            if bytes.len() < after_element {
+                return Err(String::from("truncated element"));
+            }
+            let element = String::from_utf8(bytes[offset..after_element].to_vec())
+                .map_err(|_| String::from("element is not valid UTF-8"))?;
+            let mut offset = after_element;
+
+            let siblings_len = n
+                .checked_mul(digest_len)
+                .ok_or_else(|| String::from("sibling count overflow"))?;
+            let after_siblings = offset
+                .checked_add(siblings_len)
+                .ok_or_else(|| String::from("sibling count overflow"))?;
+            if bytes.len() < after_siblings {
+                return Err(String::from("truncated siblings"));
             }
-            // println!("current_hash: {}", current_hash);
+            let mut siblings = Vec::with_capacity(n);
+            for _ in 0..n {
+                siblings.push(bytes[offset..offset + digest_len].to_vec());
+                offset += digest_len;
+            }
+
+            let direction_bytes = n.div_ceil(8);
+            let after_directions = offset
+                .checked_add(direction_bytes)
+                .ok_or_else(|| String::from("direction count overflow"))?;
+            if bytes.len() < after_directions {
+                return Err(String::from("truncated directions"));
+            }
+            let mut directions = Vec::with_capacity(n);
+            for i in 0..n {
+                let byte = bytes[offset + i / 8];
+                directions.push((byte >> (i % 8)) & 1 == 1);
+            }
+
+            Ok(MerkleProof {
+                element,
+                siblings,
+                directions,
+                _marker: PhantomData,
+            })
         }
 
-        current_hash == root
+        // Hex-string conveniences around the binary layout.
+        pub fn to_hex(&self) -> String {
+            hex::encode(self.to_bytes())
+        }
+
+        pub fn from_hex(s: &str) -> Result<Self, String> {
+            let bytes = hex::decode(s).map_err(|e| e.to_string())?;
+            Self::from_bytes(&bytes)
+        }
     }
 
     pub fn generate_random_string(length: usize) -> String {
@@ -240,52 +363,41 @@ pub mod merkle_tree {
     // Updates the Merkle tree (from leaf to root) to include the new element at index.
     // For simplicity, the index must be within the bounds of the original vector size.
     // If it is not, return an error.
-    pub fn update_element(
-        t: &MerkleTree,
+    pub fn update_element<D: Digest>(
+        t: &MerkleTree<D>,
         index: usize,
         element: &str,
-    ) -> Result<MerkleTree, String> {
-        // let mut path = Vec::new();
-        // let height = (collect_elements(&t.root).len() as f32).log2() as usize;
+    ) -> Result<MerkleTree<D>, String> {
+        if index >= t.leaves.len() {
+            return Err(String::from("Index out of bounds"));
+        }
 
-        let mut current_node = &t.root;
-        let mut updated_node_list = Vec::new();
+        let mut nodes = t.nodes.clone();
+        let mut leaves = t.leaves.clone();
 
-        let proof = get_proof(t, index);
-        match proof {
-            Ok(p, ..) => {
-                for direction in &p.directions {
-                    if let Node::Branch { left, right, .. } = current_node {
-                        current_node = if *direction { right } else { left };
-                        updated_node_list.push(current_node);
-                    }
-                }
-                updated_node_list.reverse();
-
-                // Update the leaf node and recompute the hashes along the path
-                let mut new_hash = hash_leaf(element).clone();
-                let mut new_node = Node::Leaf {
-                    hash: new_hash.clone(),
-                    data: element.to_string().clone(),
-                };
-
-                for i in 0..updated_node_list.len() {
-                    if p.directions[i] {
-                        *updated_node_list[i + 1] = Node::Branch {
-                            hash: hash_node(&new_hash.clone(), &p.siblings[i]),
-                            left: Box::new(new_node),
-                            right: Box::new(*updated_node_list[i]),
-                        };
-                    } else {
-                        new_hash = hash_node(&p.siblings[i], &new_hash);
-                    }
-                    // println!("current_hash: {}", current_hash);
-                }
+        // replace the leaf, then recompute only the single path up to the root
+        leaves[index] = element.to_string();
+        nodes[t.level_offsets[0] + index] = hash_leaf::<D>(element);
 
-                Ok(MerkleTree { root: new_node })
-            }
-            Err(e) => Err(e),
+        let mut i = index;
+        let mut level = 0;
+        while level + 1 < t.level_offsets.len() {
+            let level_start = t.level_offsets[level];
+            let parent_start = t.level_offsets[level + 1];
+            let left = i & !1; // index of the left child in the pair
+            let l = nodes[level_start + left].clone();
+            let r = nodes[level_start + left + 1].clone();
+            nodes[parent_start + i / 2] = hash_node::<D>(&l, &r);
+            i /= 2;
+            level += 1;
         }
+
+        Ok(MerkleTree {
+            nodes,
+            leaves,
+            level_offsets: t.level_offsets.clone(),
+            _marker: PhantomData,
+        })
     }
 
     // ** BONUS (optional - hard) **
@@ -298,14 +410,575 @@ pub mod merkle_tree {
     //
     // The aggregated proof size should generally be smaller than
     // that of the naive approach (calling GetProof for every index).
-    // pub fn get_aggregate_proof(t: &MerkleTree, start_index: usize, end_index: usize) -> () {
-    //     // TODO
-    // }
+    pub fn get_aggregate_proof<D: Digest>(
+        t: &MerkleTree<D>,
+        start_index: usize,
+        end_index: usize,
+    ) -> Result<AggregateProof<D>, String> {
+        let elements = t.leaves.clone();
+        let num_elements = elements.len();
+
+        if start_index >= end_index {
+            return Err(String::from("start_index must be less than end_index"));
+        }
+        if end_index > num_elements {
+            return Err(String::from("Index out of bounds"));
+        }
+
+        // level 0 holds the leaf hashes; each subsequent level is the parent row.
+        let levels = build_levels::<D>(&elements);
+
+        let covered = elements[start_index..end_index].to_vec();
+        let mut siblings = Vec::new();
+
+        // covered interval of node indices at the current level
+        let mut lo = start_index;
+        let mut hi = end_index - 1;
+        let mut level = 0;
+
+        while levels[level].len() > 1 {
+            // the left boundary's sibling sits outside the range only when lo is odd
+            if lo % 2 == 1 {
+                siblings.push(AggregateSibling {
+                    level,
+                    left: true,
+                    hash: levels[level][lo - 1].clone(),
+                });
+            }
+            // the right boundary's sibling sits outside the range only when hi is even
+            if hi.is_multiple_of(2) {
+                siblings.push(AggregateSibling {
+                    level,
+                    left: false,
+                    hash: levels[level][hi + 1].clone(),
+                });
+            }
+            lo /= 2;
+            hi /= 2;
+            level += 1;
+        }
+
+        Ok(AggregateProof {
+            start_index,
+            elements: covered,
+            siblings,
+            _marker: PhantomData,
+        })
+    }
+
+    // verify an aggregate proof by rebuilding the tree level by level: hash the
+    // adjacent covered pairs together, splice in the stored boundary siblings in
+    // the correct left/right order, and repeat until a single root hash remains.
+    pub fn verify_aggregate_proof<D: Digest>(root: String, proof: &AggregateProof<D>) -> bool {
+        if proof.elements.is_empty() {
+            return false;
+        }
+
+        let mut current: Vec<Vec<u8>> = proof.elements.iter().map(|e| hash_leaf::<D>(e)).collect();
+        let mut lo = proof.start_index;
+        let mut hi = proof.start_index + proof.elements.len() - 1;
+        let mut level = 0;
+
+        // keep folding while more than one hash remains at this level, or while
+        // a recorded boundary sibling at or above this level still needs to be
+        // spliced in; stopping on `current.len() == 1` alone would return before
+        // consuming siblings recorded for the levels above a single-element
+        // (or otherwise already-collapsed) covered range.
+        while current.len() > 1 || proof.siblings.iter().any(|s| s.level >= level) {
+            let mut combined = Vec::with_capacity(current.len() + 2);
+
+            if lo % 2 == 1 {
+                match find_aggregate_sibling(proof, level, true) {
+                    Some(hash) => combined.push(hash),
+                    None => return false,
+                }
+            }
+            combined.extend(current.iter().cloned());
+            if hi.is_multiple_of(2) {
+                match find_aggregate_sibling(proof, level, false) {
+                    Some(hash) => combined.push(hash),
+                    None => return false,
+                }
+            }
+
+            if combined.len() % 2 != 0 {
+                return false;
+            }
+
+            let mut next = Vec::with_capacity(combined.len() / 2);
+            let mut i = 0;
+            while i < combined.len() {
+                next.push(hash_node::<D>(&combined[i], &combined[i + 1]));
+                i += 2;
+            }
+
+            current = next;
+            lo /= 2;
+            hi /= 2;
+            level += 1;
+        }
+
+        hex::encode(&current[0]) == root
+    }
+
+    // build the full level-by-level array of node hashes from the padded leaves,
+    // where level 0 is the leaves and the last level is the single root hash.
+    fn build_levels<D: Digest>(elements: &[String]) -> Vec<Vec<Vec<u8>>> {
+        let mut levels = vec![elements.iter().map(|e| hash_leaf::<D>(e)).collect::<Vec<_>>()];
+
+        while levels.last().unwrap().len() > 1 {
+            let current = levels.last().unwrap();
+            let mut parents = Vec::with_capacity(current.len() / 2);
+            let mut i = 0;
+            while i < current.len() {
+                parents.push(hash_node::<D>(&current[i], &current[i + 1]));
+                i += 2;
+            }
+            levels.push(parents);
+        }
+
+        levels
+    }
+
+    fn find_aggregate_sibling<D>(
+        proof: &AggregateProof<D>,
+        level: usize,
+        left: bool,
+    ) -> Option<Vec<u8>> {
+        proof
+            .siblings
+            .iter()
+            .find(|s| s.level == level && s.left == left)
+            .map(|s| s.hash.clone())
+    }
+
+    // default depth for a sparse tree keyed by a full SHA-256 element hash.
+    pub const DEFAULT_SPARSE_DEPTH: usize = 256;
+
+    // a fixed-depth Merkle tree that addresses leaves by the bits of their element
+    // hash rather than packing them left to right. empty subtrees are never
+    // materialized: an absent subtree at height `i` resolves to ZERO_HASHES[i] in
+    // O(1), so the root is well-defined even for addresses that hold no element.
+    // this makes the structure usable as an authenticated key-value map.
+    pub struct SparseMerkleTree<D = Sha256> {
+        depth: usize,
+        zero_hashes: Vec<Vec<u8>>, // ZERO_HASHES[i] = hash of an empty subtree of height i
+        root: Node,
+        _marker: PhantomData<D>,
+    }
+
+    // create an empty sparse tree of the given depth, precomputing the zero-hash
+    // table once: ZERO_HASHES[0] = hash_leaf(""), ZERO_HASHES[i] = hash_node of the
+    // previous level with itself.
+    pub fn create_sparse_merkle_tree<D: Digest>(depth: usize) -> SparseMerkleTree<D> {
+        let mut zero_hashes = Vec::with_capacity(depth + 1);
+        zero_hashes.push(hash_leaf::<D>(""));
+        for i in 1..=depth {
+            zero_hashes.push(hash_node::<D>(&zero_hashes[i - 1], &zero_hashes[i - 1]));
+        }
+
+        SparseMerkleTree {
+            depth,
+            zero_hashes,
+            root: Node::Empty,
+            _marker: PhantomData,
+        }
+    }
+
+    // the root hash of a sparse tree; an empty root resolves to the top zero hash.
+    pub fn sparse_root<D>(t: &SparseMerkleTree<D>) -> String {
+        hex::encode(subtree_hash(&t.root, t.depth, &t.zero_hashes))
+    }
+
+    // insert (or overwrite) an element at the address derived from its hash.
+    pub fn sparse_insert<D: Digest>(t: &mut SparseMerkleTree<D>, element: &str) {
+        let address = element_address::<D>(element, t.depth);
+        let root = std::mem::replace(&mut t.root, Node::Empty);
+        t.root = sparse_insert_node::<D>(root, &address, 0, t.depth, &t.zero_hashes, element);
+    }
+
+    fn sparse_insert_node<D: Digest>(
+        node: Node,
+        address: &[bool],
+        idx: usize,
+        depth: usize,
+        zero_hashes: &[Vec<u8>],
+        element: &str,
+    ) -> Node {
+        if idx == depth {
+            return Node::Leaf {
+                hash: hash_leaf::<D>(element),
+                data: element.to_string(),
+            };
+        }
+
+        let (mut left, mut right) = match node {
+            Node::Branch { left, right, .. } => (*left, *right),
+            _ => (Node::Empty, Node::Empty),
+        };
+
+        if address[idx] {
+            right = sparse_insert_node::<D>(right, address, idx + 1, depth, zero_hashes, element);
+        } else {
+            left = sparse_insert_node::<D>(left, address, idx + 1, depth, zero_hashes, element);
+        }
+
+        // the children sit one level below this node
+        let child_height = depth - (idx + 1);
+        let hash = hash_node::<D>(
+            &subtree_hash(&left, child_height, zero_hashes),
+            &subtree_hash(&right, child_height, zero_hashes),
+        );
+
+        Node::Branch {
+            hash,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    // prove that `element` is *absent* from the sparse tree. the returned proof
+    // has the same shape as an inclusion proof, but its terminal node is the empty
+    // leaf at the element's address: verification folds the siblings up from the
+    // zero leaf and checks the result against the root. returns an error if the
+    // element is actually present.
+    pub fn get_non_inclusion_proof<D: Digest>(
+        t: &SparseMerkleTree<D>,
+        element: &str,
+    ) -> Result<MerkleProof<D>, String> {
+        let address = element_address::<D>(element, t.depth);
+
+        let mut node = &t.root;
+        let mut siblings = Vec::new();
+        let mut directions = Vec::new();
+
+        for (idx, &went_right) in address.iter().enumerate() {
+            let child_height = t.depth - (idx + 1);
+
+            match node {
+                Node::Branch { left, right, .. } => {
+                    if went_right {
+                        siblings.push(subtree_hash(left, child_height, &t.zero_hashes));
+                        directions.push(false);
+                        node = right;
+                    } else {
+                        siblings.push(subtree_hash(right, child_height, &t.zero_hashes));
+                        directions.push(true);
+                        node = left;
+                    }
+                }
+                // the rest of the path is an empty subtree: every remaining sibling
+                // is the zero hash of its height, and the node stays empty.
+                Node::Empty => {
+                    siblings.push(t.zero_hashes[child_height].clone());
+                    directions.push(!went_right);
+                }
+                Node::Leaf { .. } => {
+                    return Err(String::from("element is present; cannot prove non-inclusion"));
+                }
+            }
+        }
+
+        if let Node::Leaf { .. } = node {
+            return Err(String::from("element is present; cannot prove non-inclusion"));
+        }
+
+        siblings.reverse();
+        directions.reverse();
+
+        Ok(MerkleProof {
+            element: element.to_string(),
+            siblings,
+            directions,
+            _marker: PhantomData,
+        })
+    }
+
+    // verify a non-inclusion proof: check that `proof.element` actually hashes to
+    // the address implied by `proof.directions` (otherwise a proof generated for
+    // one absent element could be relabeled to claim absence of any other), then
+    // fold the siblings up from the empty leaf (ZERO_HASHES[0]) and compare the
+    // result to the known root.
+    pub fn verify_non_inclusion_proof<D: Digest>(root: String, proof: &MerkleProof<D>) -> bool {
+        let depth = proof.siblings.len();
+        if proof.directions.len() != depth {
+            return false;
+        }
+
+        // directions are stored leaf-to-root; the address is root-to-leaf (most
+        // significant bit first), and direction == !address_bit at every level.
+        let claimed_address = element_address::<D>(&proof.element, depth);
+        for (i, direction) in proof.directions.iter().enumerate() {
+            let address_bit = claimed_address[depth - 1 - i];
+            if *direction == address_bit {
+                return false;
+            }
+        }
+
+        let mut current_hash = hash_leaf::<D>("");
+
+        for (sibling_hash, direction) in proof.siblings.iter().zip(proof.directions.iter()) {
+            if *direction {
+                current_hash = hash_node::<D>(&current_hash, sibling_hash);
+            } else {
+                current_hash = hash_node::<D>(sibling_hash, &current_hash);
+            }
+        }
+
+        hex::encode(current_hash) == root
+    }
+
+    // the hash a subtree contributes to its parent: a materialized node carries its
+    // own hash, while an empty subtree of the given height resolves to its zero hash.
+    fn subtree_hash(node: &Node, height: usize, zero_hashes: &[Vec<u8>]) -> Vec<u8> {
+        match node {
+            Node::Empty => zero_hashes[height].clone(),
+            Node::Leaf { hash, .. } | Node::Branch { hash, .. } => hash.clone(),
+        }
+    }
+
+    // the fixed-depth address of an element: the high bits of its leaf hash, most
+    // significant bit first, truncated or zero-extended to `depth` bits.
+    fn element_address<D: Digest>(element: &str, depth: usize) -> Vec<bool> {
+        let hash = hash_leaf::<D>(element);
+        let mut bits = Vec::with_capacity(depth);
+
+        for byte in &hash {
+            for shift in (0..8).rev() {
+                if bits.len() == depth {
+                    return bits;
+                }
+                bits.push((byte >> shift) & 1 == 1);
+            }
+        }
+
+        while bits.len() < depth {
+            bits.push(false);
+        }
+        bits
+    }
+
+    // a single stored node, keyed by its own hash. a branch records the hashes of
+    // its two children (which are themselves keys), so shared subtrees are stored
+    // once and an update only writes the new nodes along the changed path.
+    #[derive(Clone)]
+    pub enum NodeRecord {
+        Leaf { data: String },
+        Branch { left: Vec<u8>, right: Vec<u8> },
+    }
+
+    // a key-value store of tree nodes, keyed by node hash. this lets a tree be
+    // built, queried, and incrementally updated against a backing store instead of
+    // living entirely in memory, enabling trees far larger than RAM.
+    pub trait NodeStore {
+        fn get(&self, key: &[u8]) -> Option<NodeRecord>;
+        fn insert(&mut self, key: Vec<u8>, record: NodeRecord);
+    }
+
+    // the default in-memory store.
+    #[derive(Default)]
+    pub struct MemoryStore {
+        nodes: HashMap<Vec<u8>, NodeRecord>,
+    }
+
+    impl MemoryStore {
+        pub fn new() -> Self {
+            MemoryStore {
+                nodes: HashMap::new(),
+            }
+        }
+    }
+
+    impl NodeStore for MemoryStore {
+        fn get(&self, key: &[u8]) -> Option<NodeRecord> {
+            self.nodes.get(key).cloned()
+        }
+
+        fn insert(&mut self, key: Vec<u8>, record: NodeRecord) {
+            self.nodes.insert(key, record);
+        }
+    }
+
+    // a handle to a tree persisted in a NodeStore: only the root hash and the leaf
+    // count live in memory; every node is loaded from the store by key on demand.
+    pub struct StoredMerkleTree<D = Sha256> {
+        root: Vec<u8>,
+        num_leaves: usize,
+        _marker: PhantomData<D>,
+    }
+
+    // the hex-encoded root hash of a store-backed tree.
+    pub fn stored_root<D>(t: &StoredMerkleTree<D>) -> String {
+        hex::encode(&t.root)
+    }
+
+    // build a tree into `store`, persisting each leaf and branch keyed by its hash,
+    // and return a handle carrying the root hash.
+    pub fn create_merkle_tree_in_store<D: Digest, S: NodeStore>(
+        store: &mut S,
+        elements: &Vec<String>,
+    ) -> Result<StoredMerkleTree<D>, String> {
+        if elements.is_empty() {
+            return Ok(StoredMerkleTree {
+                root: Vec::new(),
+                num_leaves: 0,
+                _marker: PhantomData,
+            });
+        }
+
+        let leaves = pad_elements(elements);
+        let num_leaves = leaves.len();
+
+        // level 0: persist every leaf keyed by its hash
+        let mut level: Vec<Vec<u8>> = Vec::with_capacity(num_leaves);
+        for element in &leaves {
+            let hash = hash_leaf::<D>(element);
+            store.insert(
+                hash.clone(),
+                NodeRecord::Leaf {
+                    data: element.clone(),
+                },
+            );
+            level.push(hash);
+        }
+
+        // each level hashes adjacent pairs and persists the resulting branch
+        while level.len() > 1 {
+            let mut parents = Vec::with_capacity(level.len() / 2);
+            let mut i = 0;
+            while i < level.len() {
+                let left = level[i].clone();
+                let right = level[i + 1].clone();
+                let hash = hash_node::<D>(&left, &right);
+                store.insert(hash.clone(), NodeRecord::Branch { left, right });
+                parents.push(hash);
+                i += 2;
+            }
+            level = parents;
+        }
+
+        Ok(StoredMerkleTree {
+            root: level[0].clone(),
+            num_leaves,
+            _marker: PhantomData,
+        })
+    }
+
+    // return an inclusion proof for `index`, loading each node from the store
+    // lazily by key as the path is walked down from the root.
+    pub fn get_proof_from_store<D, S: NodeStore>(
+        store: &S,
+        t: &StoredMerkleTree<D>,
+        index: usize,
+    ) -> Result<MerkleProof<D>, String> {
+        if index >= t.num_leaves {
+            return Err(String::from("Index out of bounds"));
+        }
+
+        let height = (t.num_leaves as f32).log2() as usize;
+        let mut current = t.root.clone();
+        let mut siblings = Vec::new();
+        let mut directions = Vec::new();
+
+        for level in 0..height {
+            let bit = (index >> (height - 1 - level)) & 1;
+            match store.get(&current) {
+                Some(NodeRecord::Branch { left, right }) => {
+                    if bit == 0 {
+                        // descend left: the sibling is on the right
+                        siblings.push(right);
+                        directions.push(true);
+                        current = left;
+                    } else {
+                        siblings.push(left);
+                        directions.push(false);
+                        current = right;
+                    }
+                }
+                _ => return Err(String::from("missing node in store")),
+            }
+        }
+
+        let element = match store.get(&current) {
+            Some(NodeRecord::Leaf { data }) => data,
+            _ => return Err(String::from("missing leaf in store")),
+        };
+
+        siblings.reverse();
+        directions.reverse();
+
+        Ok(MerkleProof {
+            element,
+            siblings,
+            directions,
+            _marker: PhantomData,
+        })
+    }
+
+    // update a single leaf, writing only the new nodes along the changed path and
+    // leaving shared subtrees untouched, and return a handle to the new root.
+    pub fn update_element_in_store<D: Digest, S: NodeStore>(
+        store: &mut S,
+        t: &StoredMerkleTree<D>,
+        index: usize,
+        element: &str,
+    ) -> Result<StoredMerkleTree<D>, String> {
+        if index >= t.num_leaves {
+            return Err(String::from("Index out of bounds"));
+        }
+
+        let height = (t.num_leaves as f32).log2() as usize;
+
+        // walk down to the leaf, remembering the sibling hash at each level
+        let mut current = t.root.clone();
+        let mut path: Vec<(usize, Vec<u8>)> = Vec::with_capacity(height);
+        for level in 0..height {
+            let bit = (index >> (height - 1 - level)) & 1;
+            match store.get(&current) {
+                Some(NodeRecord::Branch { left, right }) => {
+                    if bit == 0 {
+                        path.push((bit, right));
+                        current = left;
+                    } else {
+                        path.push((bit, left));
+                        current = right;
+                    }
+                }
+                _ => return Err(String::from("missing node in store")),
+            }
+        }
+
+        // write the new leaf, then recompute and persist the path back up
+        let mut new_hash = hash_leaf::<D>(element);
+        store.insert(
+            new_hash.clone(),
+            NodeRecord::Leaf {
+                data: element.to_string(),
+            },
+        );
+
+        for (bit, sibling) in path.iter().rev() {
+            let (left, right) = if *bit == 0 {
+                (new_hash.clone(), sibling.clone())
+            } else {
+                (sibling.clone(), new_hash.clone())
+            };
+            new_hash = hash_node::<D>(&left, &right);
+            store.insert(new_hash.clone(), NodeRecord::Branch { left, right });
+        }
+
+        Ok(StoredMerkleTree {
+            root: new_hash,
+            num_leaves: t.num_leaves,
+            _marker: PhantomData,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::merkle_tree::*;
+    use sha2::Sha256;
 
     #[test]
     fn test_root() {
@@ -315,15 +988,15 @@ mod tests {
             "elements".to_string(),
         ];
 
-        let expected_root = hash_node(
-            &hash_node(&hash_leaf("some"), &hash_leaf("test")),
-            &hash_node(&hash_leaf("elements"), &hash_leaf("")),
+        let expected_root = hash_node::<Sha256>(
+            &hash_node::<Sha256>(&hash_leaf::<Sha256>("some"), &hash_leaf::<Sha256>("test")),
+            &hash_node::<Sha256>(&hash_leaf::<Sha256>("elements"), &hash_leaf::<Sha256>("")),
         );
 
-        let mt = create_merkle_tree(&elements);
+        let mt = create_merkle_tree::<Sha256>(&elements);
 
         match mt {
-            Ok(mt) => assert_eq!(get_root(&mt), expected_root),
+            Ok(mt) => assert_eq!(get_root(&mt), hex::encode(expected_root)),
             Err(e) => println!("{}", e),
         }
     }
@@ -335,7 +1008,7 @@ mod tests {
             "test".to_string(),
             "elements".to_string(),
         ];
-        let mt = create_merkle_tree(&elements);
+        let mt = create_merkle_tree::<Sha256>(&elements);
 
         match mt {
             Ok(mt) => {
@@ -343,12 +1016,7 @@ mod tests {
                     let proof = get_proof(&mt, i);
 
                     match proof {
-                        Ok(p) => {
-                            // println!("\n-------- {}", p.element);
-                            // println!("-------- {:?}", p.siblings);
-                            // println!("-------- {:?}\n", p.directions);
-                            assert!(verify_proof(get_root(&mt), &p))
-                        }
+                        Ok(p) => assert!(verify_proof(get_root(&mt), &p)),
                         Err(e) => println!("{}", e),
                     }
                 }
@@ -359,8 +1027,8 @@ mod tests {
 
     #[test]
     fn test_empty() {
-        let mut elements = Vec::new();
-        let mt = create_merkle_tree(&elements);
+        let elements = Vec::new();
+        let mt = create_merkle_tree::<Sha256>(&elements);
 
         let expected_root = String::new();
 
@@ -373,7 +1041,7 @@ mod tests {
     #[test]
     fn test_big_tree() {
         let elements: Vec<String> = (0..1000).map(|_| generate_random_string(10)).collect();
-        let mt = create_merkle_tree(&elements);
+        let mt = create_merkle_tree::<Sha256>(&elements);
 
         match mt {
             Ok(mt) => {
@@ -381,15 +1049,133 @@ mod tests {
                     let proof = get_proof(&mt, i);
 
                     match proof {
-                        Ok(p) => {
-                            // println!("\n-------- {}", p.element);
-                            // println!("-------- {:?}", p.siblings);
-                            // println!("-------- {:?}\n", p.directions);
-                            assert!(verify_proof(get_root(&mt), &p))
-                        }
+                        Ok(p) => assert!(verify_proof(get_root(&mt), &p)),
+                        Err(e) => println!("{}", e),
+                    }
+                }
+            }
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    #[test]
+    fn test_aggregate_proof() {
+        let elements: Vec<String> = (0..8).map(|_| generate_random_string(10)).collect();
+        let mt = create_merkle_tree::<Sha256>(&elements);
+
+        match mt {
+            Ok(mt) => {
+                let proof = get_aggregate_proof(&mt, 2, 6);
+
+                match proof {
+                    Ok(p) => assert!(verify_aggregate_proof(get_root(&mt), &p)),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    #[test]
+    fn test_sparse_tree() {
+        // an empty sparse tree's root is the top zero hash, and it stays
+        // well-defined as elements are inserted at their hashed addresses.
+        let mut t = create_sparse_merkle_tree::<Sha256>(DEFAULT_SPARSE_DEPTH);
+        let empty_root = sparse_root(&t);
+
+        sparse_insert(&mut t, "some");
+        let after_one = sparse_root(&t);
+        assert_ne!(empty_root, after_one);
+
+        sparse_insert(&mut t, "test");
+        assert_ne!(after_one, sparse_root(&t));
+    }
+
+    #[test]
+    fn test_non_inclusion_proof() {
+        let mut t = create_sparse_merkle_tree::<Sha256>(DEFAULT_SPARSE_DEPTH);
+        sparse_insert(&mut t, "some");
+        sparse_insert(&mut t, "test");
+
+        // an absent element yields a verifiable non-inclusion proof
+        let proof = get_non_inclusion_proof(&t, "elements");
+        match proof {
+            Ok(p) => assert!(verify_non_inclusion_proof(sparse_root(&t), &p)),
+            Err(e) => println!("{}", e),
+        }
+
+        // a present element cannot be proven absent
+        assert!(get_non_inclusion_proof(&t, "some").is_err());
+    }
+
+    #[test]
+    fn test_stored_tree() {
+        let elements = vec![
+            "some".to_string(),
+            "test".to_string(),
+            "elements".to_string(),
+        ];
+
+        let mut store = MemoryStore::new();
+        let tree = create_merkle_tree_in_store::<Sha256, _>(&mut store, &elements);
+
+        match tree {
+            Ok(tree) => {
+                // proofs loaded from the store verify against the stored root, and
+                // an update only writes the new path while leaving the root valid.
+                for i in 0..elements.len() {
+                    let proof = get_proof_from_store(&store, &tree, i);
+                    match proof {
+                        Ok(p) => assert!(verify_proof(stored_root(&tree), &p)),
                         Err(e) => println!("{}", e),
                     }
                 }
+
+                let updated = update_element_in_store(&mut store, &tree, 0, "updated");
+                match updated {
+                    Ok(updated) => {
+                        let proof = get_proof_from_store(&store, &updated, 0);
+                        match proof {
+                            Ok(p) => {
+                                assert_eq!(p.element, "updated");
+                                assert!(verify_proof(stored_root(&updated), &p));
+                            }
+                            Err(e) => println!("{}", e),
+                        }
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    #[test]
+    fn test_proof_serialization() {
+        let elements = vec![
+            "some".to_string(),
+            "test".to_string(),
+            "elements".to_string(),
+        ];
+        let mt = create_merkle_tree::<Sha256>(&elements);
+
+        match mt {
+            Ok(mt) => {
+                let proof = get_proof(&mt, 1);
+                match proof {
+                    Ok(p) => {
+                        // binary and hex forms both round-trip and still verify
+                        let decoded = MerkleProof::<Sha256>::from_bytes(&p.to_bytes()).unwrap();
+                        assert_eq!(decoded.element, p.element);
+                        assert_eq!(decoded.siblings, p.siblings);
+                        assert_eq!(decoded.directions, p.directions);
+                        assert!(verify_proof(get_root(&mt), &decoded));
+
+                        let from_hex = MerkleProof::<Sha256>::from_hex(&p.to_hex()).unwrap();
+                        assert!(verify_proof(get_root(&mt), &from_hex));
+                    }
+                    Err(e) => println!("{}", e),
+                }
             }
             Err(e) => println!("{}", e),
         }
@@ -403,11 +1189,28 @@ mod tests {
             "elements".to_string(),
         ];
 
-        let mt = create_merkle_tree(&elements);
+        let mt = create_merkle_tree::<Sha256>(&elements);
 
         match mt {
             Ok(mt) => {
-                update_element(&mt, 0, "updated");
+                let updated = update_element(&mt, 0, "updated");
+                match updated {
+                    Ok(updated) => {
+                        // the new root reflects the change, and a proof fetched
+                        // from the updated tree verifies the new leaf
+                        assert_ne!(get_root(&mt), get_root(&updated));
+
+                        let proof = get_proof(&updated, 0);
+                        match proof {
+                            Ok(p) => {
+                                assert_eq!(p.element, "updated");
+                                assert!(verify_proof(get_root(&updated), &p));
+                            }
+                            Err(e) => println!("{}", e),
+                        }
+                    }
+                    Err(e) => println!("{}", e),
+                }
             }
             Err(e) => println!("{}", e),
         }